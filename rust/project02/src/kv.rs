@@ -13,6 +13,10 @@ use std::path::{Path, PathBuf};
 use std::result;
 use std::str::FromStr;
 
+/// The number of bytes of stale commands a log may accumulate before
+/// `KvStore` triggers a compaction.
+const COMPACTION_THRESHOLD: u64 = 1024 * 1024;
+
 #[derive(Debug)]
 pub struct KvStore {
     path: PathBuf,
@@ -20,6 +24,10 @@ pub struct KvStore {
     index: BTreeMap<String, CommandPos>,
     log: BufWriterWithPos<File>,
     current_generation: u64,
+    /// Number of bytes that could be saved by a compaction, i.e. the total
+    /// size of commands in the log that have since been overwritten or
+    /// removed.
+    uncompacted: u64,
 }
 
 impl KvStore {
@@ -31,17 +39,18 @@ impl KvStore {
 
         let mut index = BTreeMap::new();
 
+        let mut uncompacted = 0;
         let gen_list = sorted_gen_list(&path)?;
         for &gen in &gen_list {
             let mut reader = BufReaderWithPos::new(File::open(log_path(&path, gen))?)?;
-            load(gen, &mut reader, &mut index)?;
+            uncompacted += load(gen, &mut reader, &mut index)?;
             store.insert(gen, reader);
         }
 
         let current_generation = gen_list.last().unwrap_or(&0) + 1;
 
-        let reader = BufReaderWithPos::new(File::create(log_path(&path, current_generation))?)?;
         let log = new_log_file(&path, current_generation)?;
+        let reader = BufReaderWithPos::new(File::open(log_path(&path, current_generation))?)?;
         store.insert(current_generation, reader);
 
         Ok(KvStore {
@@ -50,6 +59,7 @@ impl KvStore {
             index,
             log,
             current_generation,
+            uncompacted,
         })
     }
 
@@ -60,8 +70,16 @@ impl KvStore {
         self.log.flush()?;
 
         if let Command::Set { key, .. } = cmd {
-            self.index
-                .insert(key, (self.current_generation, pos..self.log.pos).into());
+            if let Some(old_cmd) = self
+                .index
+                .insert(key, (self.current_generation, pos..self.log.pos).into())
+            {
+                self.uncompacted += old_cmd.len;
+            }
+        }
+
+        if self.uncompacted > COMPACTION_THRESHOLD {
+            self.compact()?;
         }
         Ok(())
     }
@@ -86,19 +104,79 @@ impl KvStore {
 
     pub fn remove(&mut self, key: String) -> Result<()> {
         if self.index.contains_key(&key) {
+            let pos = self.log.pos;
             let cmd = Command::remove(key);
             serde_json::to_writer(&mut self.log, &cmd)?;
             self.log.flush()?;
+            self.uncompacted += self.log.pos - pos;
             if let Command::Remove { key } = cmd {
-                self.index.remove(&key).expect("key not found");
+                let old_cmd = self.index.remove(&key).expect("key not found");
+                self.uncompacted += old_cmd.len;
+            }
+
+            if self.uncompacted > COMPACTION_THRESHOLD {
+                self.compact()?;
             }
             Ok(())
         } else {
             Err(KvsError::KeyNotFound)
         }
     }
+
+    /// Clears stale entries in the log by rewriting every command still
+    /// reachable from `self.index` into a fresh generation, then dropping
+    /// every generation older than it.
+    fn compact(&mut self) -> Result<()> {
+        let compaction_gen = self.current_generation + 1;
+        let mut compaction_writer = new_log_file(&self.path, compaction_gen)?;
+        self.store.insert(
+            compaction_gen,
+            BufReaderWithPos::new(File::open(log_path(&self.path, compaction_gen))?)?,
+        );
+
+        let mut new_pos = 0;
+        for cmd_pos in &mut self.index.values_mut() {
+            let reader = self
+                .store
+                .get_mut(&cmd_pos.gen)
+                .expect("Can not get reader by cmd.gen");
+            if reader.pos != cmd_pos.pos {
+                reader.seek(SeekFrom::Start(cmd_pos.pos))?;
+            }
+
+            let mut entry_reader = reader.take(cmd_pos.len);
+            let len = io::copy(&mut entry_reader, &mut compaction_writer)?;
+            *cmd_pos = (compaction_gen, new_pos..new_pos + len).into();
+            new_pos += len;
+        }
+        compaction_writer.flush()?;
+
+        self.current_generation = compaction_gen + 1;
+        self.log = new_log_file(&self.path, self.current_generation)?;
+        self.store.insert(
+            self.current_generation,
+            BufReaderWithPos::new(File::open(log_path(&self.path, self.current_generation))?)?,
+        );
+
+        let stale_gens: Vec<_> = self
+            .store
+            .keys()
+            .filter(|&&gen| gen < compaction_gen)
+            .cloned()
+            .collect();
+        for stale_gen in stale_gens {
+            self.store.remove(&stale_gen);
+            fs::remove_file(log_path(&self.path, stale_gen))?;
+        }
+        self.uncompacted = 0;
+
+        Ok(())
+    }
 }
 
+/// Replays the commands in the given generation's log into `index`,
+/// returning the number of bytes made stale by commands superseding an
+/// earlier entry (overwrites and removals).
 fn load(
     gen: u64,
     reader: &mut BufReaderWithPos<File>,
@@ -106,19 +184,24 @@ fn load(
 ) -> Result<u64> {
     let mut pos = reader.seek(SeekFrom::Start(0))?;
     let mut stream = Deserializer::from_reader(reader).into_iter::<Command>();
+    let mut uncompacted = 0;
     while let Some(cmd) = stream.next() {
         let new_pos = stream.byte_offset() as u64;
         match cmd? {
             Command::Set { key, .. } => {
-                index.insert(key, (gen, pos..new_pos).into());
+                if let Some(old_cmd) = index.insert(key, (gen, pos..new_pos).into()) {
+                    uncompacted += old_cmd.len;
+                }
             }
             Command::Remove { key } => {
-                index.remove(&key).expect("key not in log file");
+                let old_cmd = index.remove(&key).expect("key not in log file");
+                uncompacted += old_cmd.len;
+                uncompacted += new_pos - pos;
             }
         }
         pos = new_pos;
     }
-    Ok(gen)
+    Ok(uncompacted)
 }
 
 /// Returns sorted generation numbers in the given directory
@@ -311,6 +394,7 @@ impl FromStr for Filename {
 #[cfg(test)]
 mod test_kv {
     use super::*;
+    use tempfile::TempDir;
 
     #[test]
     fn test_log_to_file() {
@@ -325,4 +409,38 @@ mod test_kv {
         let filename = Filename::from("0000000000000001");
         assert_eq!(Filename::new(1), filename);
     }
+
+    fn dir_size(path: &Path) -> u64 {
+        fs::read_dir(path)
+            .unwrap()
+            .flat_map(|res| res.map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension() == Some("log".as_ref()))
+            .fold(0, |acc, path| acc + fs::metadata(path).unwrap().len())
+    }
+
+    #[test]
+    fn compaction_shrinks_log_on_repeated_overwrites() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut store = KvStore::open(temp_dir.path()).unwrap();
+
+        let mut sizes = Vec::new();
+        for iter in 0..60_000u64 {
+            store
+                .set("key".to_owned(), format!("value-{}", iter))
+                .unwrap();
+            if iter % 1000 == 0 {
+                sizes.push(dir_size(temp_dir.path()));
+            }
+        }
+
+        assert!(
+            sizes.windows(2).any(|w| w[1] < w[0]),
+            "expected at least one compaction to shrink the on-disk log, sizes recorded: {:?}",
+            sizes
+        );
+        assert_eq!(
+            store.get("key".to_owned()).unwrap(),
+            Some("value-59999".to_owned())
+        );
+    }
 }
@@ -0,0 +1,123 @@
+#[macro_use]
+extern crate log;
+
+extern crate env_logger;
+
+use clap::{App, AppSettings, Arg, SubCommand};
+
+use kvs::{Request, Response, Result};
+
+use serde_json::Deserializer;
+use std::io::{BufReader, BufWriter, Write};
+use std::net::TcpStream;
+use std::process::exit;
+
+const DEFAULT_LISTENING_ADDRESS: &str = "127.0.0.1:4000";
+
+fn main() -> Result<()> {
+    env_logger::init();
+
+    let addr_arg = || {
+        Arg::with_name("addr")
+            .long("addr")
+            .takes_value(true)
+            .help("the server address, e.g. 127.0.0.1:4000")
+    };
+
+    let matches = App::new(env!("CARGO_PKG_NAME"))
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about(env!("CARGO_PKG_DESCRIPTION"))
+        .setting(AppSettings::DisableHelpSubcommand)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .subcommand(
+            SubCommand::with_name("set")
+                .about("Set the value of a string key to a string")
+                .arg(Arg::with_name("KEY").help("A string key").required(true))
+                .arg(
+                    Arg::with_name("VALUE")
+                        .help("The string value of the key")
+                        .required(true),
+                )
+                .arg(addr_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("get")
+                .about("Get the string value of a given string key")
+                .arg(Arg::with_name("KEY").help("A string key").required(true))
+                .arg(addr_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("rm")
+                .about("Remove a given key")
+                .arg(Arg::with_name("KEY").help("A string key").required(true))
+                .arg(addr_arg()),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        ("set", Some(matches)) => {
+            let key = matches.value_of("KEY").expect("KEY argument missing");
+            let value = matches.value_of("VALUE").expect("VALUE argument missing");
+            let addr = matches.value_of("addr").unwrap_or(DEFAULT_LISTENING_ADDRESS);
+            let request = Request::Set {
+                key: key.to_string(),
+                value: value.to_string(),
+            };
+            match send(addr, request)? {
+                Response::Ok(_) => {}
+                Response::Err(msg) => {
+                    error!("{}", msg);
+                    exit(1);
+                }
+            }
+        }
+        ("get", Some(matches)) => {
+            let key = matches.value_of("KEY").expect("KEY argument missing");
+            let addr = matches.value_of("addr").unwrap_or(DEFAULT_LISTENING_ADDRESS);
+            let request = Request::Get {
+                key: key.to_string(),
+            };
+            match send(addr, request)? {
+                Response::Ok(Some(value)) => println!("{}", value),
+                Response::Ok(None) => println!("Key not found"),
+                Response::Err(msg) => {
+                    error!("{}", msg);
+                    exit(1);
+                }
+            }
+        }
+        ("rm", Some(matches)) => {
+            let key = matches.value_of("KEY").expect("KEY argument missing");
+            let addr = matches.value_of("addr").unwrap_or(DEFAULT_LISTENING_ADDRESS);
+            let request = Request::Remove {
+                key: key.to_string(),
+            };
+            match send(addr, request)? {
+                Response::Ok(_) => {}
+                Response::Err(msg) => {
+                    error!("{}", msg);
+                    exit(1);
+                }
+            }
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+/// Sends a single request to `addr` and waits for its response.
+fn send(addr: &str, request: Request) -> Result<Response> {
+    let stream = TcpStream::connect(addr)?;
+    let mut writer = BufWriter::new(&stream);
+    serde_json::to_writer(&mut writer, &request)?;
+    writer.flush()?;
+
+    let reader = BufReader::new(&stream);
+    Deserializer::from_reader(reader)
+        .into_iter::<Response>()
+        .next()
+        .expect("No response from server")
+        .map_err(Into::into)
+}
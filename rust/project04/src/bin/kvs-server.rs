@@ -0,0 +1,84 @@
+#[macro_use]
+extern crate log;
+
+extern crate env_logger;
+
+use clap::{App, Arg};
+
+use kvs::{KvStore, KvsError, KvsServer, Result, SledKvsEngine};
+
+use std::env::current_dir;
+use std::fs;
+use std::path::Path;
+
+const DEFAULT_LISTENING_ADDRESS: &str = "127.0.0.1:4000";
+const DEFAULT_ENGINE: &str = "kvs";
+const ENGINE_FILE: &str = "engine";
+
+fn main() -> Result<()> {
+    env_logger::init();
+
+    let matches = App::new(env!("CARGO_PKG_NAME"))
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about(env!("CARGO_PKG_DESCRIPTION"))
+        .arg(
+            Arg::with_name("addr")
+                .long("addr")
+                .takes_value(true)
+                .help("the address to listen on, e.g. 127.0.0.1:4000"),
+        )
+        .arg(
+            Arg::with_name("engine")
+                .long("engine")
+                .takes_value(true)
+                .possible_values(&["kvs", "sled"])
+                .help("the storage engine to use"),
+        )
+        .get_matches();
+
+    let addr = matches.value_of("addr").unwrap_or(DEFAULT_LISTENING_ADDRESS);
+    let data_path = current_dir()?;
+
+    let engine = current_engine(&data_path, matches.value_of("engine"))?;
+    write_engine(&data_path, &engine)?;
+
+    info!("kvs-server {}", env!("CARGO_PKG_VERSION"));
+    info!("storage engine: {}", engine);
+    info!("listening on {}", addr);
+
+    match engine.as_str() {
+        "sled" => KvsServer::new(SledKvsEngine::open(&data_path)?).run(addr),
+        _ => KvsServer::new(KvStore::open(&data_path)?).run(addr),
+    }
+}
+
+/// Determines which engine this invocation should use.
+///
+/// Without `--engine`, a data directory that was previously opened keeps
+/// the engine recorded in it, and a fresh one uses the `kvs` default.
+/// Explicitly requesting an engine other than the recorded one is rejected
+/// with `KvsError::WrongEngine`.
+fn current_engine(data_path: &Path, requested: Option<&str>) -> Result<String> {
+    let engine_file = data_path.join(ENGINE_FILE);
+    if !engine_file.exists() {
+        return Ok(requested.unwrap_or(DEFAULT_ENGINE).to_owned());
+    }
+
+    let stored = fs::read_to_string(&engine_file)?;
+    if let Some(requested) = requested {
+        if requested != stored {
+            return Err(KvsError::WrongEngine {
+                stored,
+                requested: requested.to_owned(),
+            });
+        }
+    }
+    Ok(stored)
+}
+
+fn write_engine(data_path: &Path, engine: &str) -> Result<()> {
+    fs::create_dir_all(data_path)?;
+    fs::write(data_path.join(ENGINE_FILE), engine)?;
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use crate::{KvsEngine, Request, Response, Result};
+use serde_json::Deserializer;
+use std::io::{BufReader, BufWriter, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+/// A server that accepts connections speaking the `kvs` wire protocol and
+/// dispatches each request to a `KvsEngine`.
+pub struct KvsServer<E: KvsEngine> {
+    engine: E,
+}
+
+impl<E: KvsEngine> KvsServer<E> {
+    /// Creates a `KvsServer` that serves requests against `engine`.
+    pub fn new(engine: E) -> Self {
+        KvsServer { engine }
+    }
+
+    /// Listens on `addr`, serving connections one at a time until the
+    /// process is killed.
+    pub fn run(mut self, addr: impl ToSocketAddrs) -> Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = self.serve(stream) {
+                        error!("Error serving client: {}", e);
+                    }
+                }
+                Err(e) => error!("Connection failed: {}", e),
+            }
+        }
+        Ok(())
+    }
+
+    fn serve(&mut self, stream: TcpStream) -> Result<()> {
+        let peer_addr = stream.peer_addr()?;
+        let reader = BufReader::new(&stream);
+        let mut writer = BufWriter::new(&stream);
+        let requests = Deserializer::from_reader(reader).into_iter::<Request>();
+
+        for request in requests {
+            let request = request?;
+            debug!("Received request from {}: {:?}", peer_addr, request);
+            let response = match request {
+                Request::Get { key } => match self.engine.get(key) {
+                    Ok(value) => Response::Ok(value),
+                    Err(e) => Response::Err(e.to_string()),
+                },
+                Request::Set { key, value } => match self.engine.set(key, value) {
+                    Ok(()) => Response::Ok(None),
+                    Err(e) => Response::Err(e.to_string()),
+                },
+                Request::Remove { key } => match self.engine.remove(key) {
+                    Ok(()) => Response::Ok(None),
+                    Err(e) => Response::Err(e.to_string()),
+                },
+            };
+            serde_json::to_writer(&mut writer, &response)?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use crate::{KvsEngine, KvsError, Result};
+use sled::Db;
+use std::path::Path;
+
+/// A `KvsEngine` backed by the `sled` embedded database.
+#[derive(Clone)]
+pub struct SledKvsEngine(Db);
+
+impl SledKvsEngine {
+    /// Opens a `SledKvsEngine` backed by the given directory.
+    pub fn open(path: impl AsRef<Path>) -> Result<SledKvsEngine> {
+        Ok(SledKvsEngine(sled::open(path)?))
+    }
+}
+
+impl KvsEngine for SledKvsEngine {
+    fn set(&mut self, key: String, value: String) -> Result<()> {
+        self.0.insert(key, value.into_bytes())?;
+        self.0.flush()?;
+        Ok(())
+    }
+
+    fn get(&mut self, key: String) -> Result<Option<String>> {
+        self.0
+            .get(key)?
+            .map(|val| String::from_utf8(val.to_vec()).map_err(Into::into))
+            .transpose()
+    }
+
+    fn remove(&mut self, key: String) -> Result<()> {
+        self.0.remove(key)?.ok_or(KvsError::KeyNotFound)?;
+        self.0.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use kvs::{Format, KvStore, KvsEngine};
+use tempfile::TempDir;
+
+const KEYS: u32 = 1000;
+
+fn write_throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("kvs_write_by_format");
+    for &format in &[Format::Json, Format::Bincode] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format),
+            &format,
+            |b, &format| {
+                b.iter_batched(
+                    || {
+                        let temp_dir = TempDir::new().unwrap();
+                        let store = KvStore::open_with_format(temp_dir.path(), format).unwrap();
+                        (temp_dir, store)
+                    },
+                    |(_temp_dir, store)| {
+                        for i in 0..KEYS {
+                            store
+                                .set(format!("key{}", i), "value".to_owned())
+                                .unwrap();
+                        }
+                    },
+                    criterion::BatchSize::SmallInput,
+                );
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, write_throughput);
+criterion_main!(benches);
@@ -0,0 +1,51 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use kvs::thread_pool::{NaiveThreadPool, SharedQueueThreadPool, ThreadPool};
+use kvs::{KvStore, KvsEngine};
+use std::sync::mpsc;
+use tempfile::TempDir;
+
+const KEYS: u32 = 1000;
+
+fn write_bench<P: ThreadPool>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group(name);
+    for &threads in &[1, 2, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(threads),
+            &threads,
+            |b, &threads| {
+                let temp_dir = TempDir::new().unwrap();
+                let engine = KvStore::open(temp_dir.path()).unwrap();
+                let pool = P::new(threads).unwrap();
+
+                b.iter(|| {
+                    let (tx, rx) = mpsc::channel();
+                    for i in 0..KEYS {
+                        let engine = engine.clone();
+                        let tx = tx.clone();
+                        pool.spawn(move || {
+                            engine
+                                .set(format!("key{}", i), "value".to_owned())
+                                .unwrap();
+                            tx.send(()).unwrap();
+                        });
+                    }
+                    for _ in 0..KEYS {
+                        rx.recv().unwrap();
+                    }
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+fn write_naive(c: &mut Criterion) {
+    write_bench::<NaiveThreadPool>(c, "kvs_write_naive_thread_pool");
+}
+
+fn write_shared_queue(c: &mut Criterion) {
+    write_bench::<SharedQueueThreadPool>(c, "kvs_write_shared_queue_thread_pool");
+}
+
+criterion_group!(benches, write_naive, write_shared_queue);
+criterion_main!(benches);
@@ -0,0 +1,23 @@
+use crate::Result;
+
+/// Trait for a key-value storage engine.
+///
+/// Implementors use interior mutability (a lock, an `Arc`-shared handle,
+/// ...) so that a single engine can be cloned and handed to the jobs of a
+/// `ThreadPool` without the server needing to serialize access itself.
+pub trait KvsEngine: Clone + Send + 'static {
+    /// Sets the value of a string key to a string.
+    ///
+    /// If the key already exists, the previous value is overwritten.
+    fn set(&self, key: String, value: String) -> Result<()>;
+
+    /// Gets the string value of a given string key.
+    ///
+    /// Returns `None` if the key does not exist.
+    fn get(&self, key: String) -> Result<Option<String>>;
+
+    /// Removes a given key.
+    ///
+    /// Returns `KvsError::KeyNotFound` if the key does not exist.
+    fn remove(&self, key: String) -> Result<()>;
+}
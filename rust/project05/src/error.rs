@@ -0,0 +1,91 @@
+use std::fmt;
+use std::io;
+use std::result;
+use std::string::FromUtf8Error;
+
+/// Error type for operations on a `KvsEngine`.
+#[derive(Debug)]
+pub enum KvsError {
+    /// IO error.
+    Io(io::Error),
+    /// Serialization or deserialization error.
+    Serde(serde_json::Error),
+    /// Error from the `sled` backing store.
+    Sled(sled::Error),
+    /// A stored value was not valid UTF-8.
+    Utf8(FromUtf8Error),
+    /// Serialization or deserialization error from the `bincode` format.
+    Bincode(bincode::Error),
+    /// Removing a key that is not in the store.
+    KeyNotFound,
+    /// A command in the log did not match the type expected at this position.
+    UnexpectedCommandType,
+    /// The data directory was previously opened with a different engine.
+    WrongEngine {
+        /// The engine recorded in the data directory.
+        stored: String,
+        /// The engine requested for this `open`.
+        requested: String,
+    },
+    /// The format marker in a data directory did not name a known `Format`.
+    UnknownFormat(String),
+}
+
+impl fmt::Display for KvsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KvsError::Io(err) => write!(f, "{}", err),
+            KvsError::Serde(err) => write!(f, "{}", err),
+            KvsError::Sled(err) => write!(f, "{}", err),
+            KvsError::Utf8(err) => write!(f, "{}", err),
+            KvsError::Bincode(err) => write!(f, "{}", err),
+            KvsError::KeyNotFound => write!(f, "Key not found"),
+            KvsError::UnexpectedCommandType => {
+                write!(f, "Unexpected command type")
+            }
+            KvsError::WrongEngine { stored, requested } => write!(
+                f,
+                "{} is not the engine this data directory was created with ({})",
+                requested, stored
+            ),
+            KvsError::UnknownFormat(format) => {
+                write!(f, "{} is not a known on-disk format", format)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KvsError {}
+
+impl From<io::Error> for KvsError {
+    fn from(err: io::Error) -> KvsError {
+        KvsError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for KvsError {
+    fn from(err: serde_json::Error) -> KvsError {
+        KvsError::Serde(err)
+    }
+}
+
+impl From<sled::Error> for KvsError {
+    fn from(err: sled::Error) -> KvsError {
+        KvsError::Sled(err)
+    }
+}
+
+impl From<FromUtf8Error> for KvsError {
+    fn from(err: FromUtf8Error) -> KvsError {
+        KvsError::Utf8(err)
+    }
+}
+
+impl From<bincode::Error> for KvsError {
+    fn from(err: bincode::Error) -> KvsError {
+        KvsError::Bincode(err)
+    }
+}
+
+/// Result type for operations on a `KvsEngine`.
+pub type Result<T> = result::Result<T, KvsError>;
@@ -0,0 +1,58 @@
+use crate::error::KvsError;
+use crate::Result;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+const MARKER_FILE: &str = "format";
+
+/// On-disk record format for the log-structured store.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON value per command, replayed with a streaming deserializer.
+    #[default]
+    Json,
+    /// A `bincode`-encoded command, length-prefixed so its byte range can
+    /// still be recovered without a streaming deserializer.
+    Bincode,
+}
+
+impl Format {
+    /// Returns the format the data directory was created with, or `None`
+    /// if it has no format marker file yet.
+    pub(crate) fn read(path: &Path) -> Result<Option<Format>> {
+        let marker = path.join(MARKER_FILE);
+        if !marker.exists() {
+            return Ok(None);
+        }
+        fs::read_to_string(marker)?.parse().map(Some)
+    }
+
+    /// Records this format as the one the data directory was created with.
+    pub(crate) fn write(self, path: &Path) -> Result<()> {
+        fs::write(path.join(MARKER_FILE), self.to_string())?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Json => write!(f, "json"),
+            Format::Bincode => write!(f, "bincode"),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = KvsError;
+
+    fn from_str(s: &str) -> Result<Format> {
+        match s {
+            "json" => Ok(Format::Json),
+            "bincode" => Ok(Format::Bincode),
+            _ => Err(KvsError::UnknownFormat(s.to_owned())),
+        }
+    }
+}
@@ -0,0 +1,616 @@
+use crate::engine::KvsEngine;
+use crate::error::{KvsError, Result};
+use crate::format::Format;
+use serde::{Deserialize, Serialize};
+use serde_json::Deserializer;
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::num::ParseIntError;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::result;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// The number of bytes of stale commands a log may accumulate before
+/// `KvStore` triggers a compaction.
+const COMPACTION_THRESHOLD: u64 = 1024 * 1024;
+
+/// A log-structured, key-value store.
+///
+/// Cloning shares the same underlying log and index (guarded by a mutex),
+/// so a `KvStore` can be handed to multiple threads, e.g. the worker jobs
+/// of a `ThreadPool`.
+#[derive(Debug, Clone)]
+pub struct KvStore(Arc<Mutex<KvStoreInner>>);
+
+#[derive(Debug)]
+struct KvStoreInner {
+    path: PathBuf,
+    store: HashMap<u64, BufReaderWithPos<File>>,
+    index: BTreeMap<String, CommandPos>,
+    log: BufWriterWithPos<File>,
+    current_generation: u64,
+    /// Number of bytes that could be saved by a compaction, i.e. the total
+    /// size of commands in the log that have since been overwritten or
+    /// removed.
+    uncompacted: u64,
+    /// The on-disk record format this store's log files are written in.
+    format: Format,
+}
+
+impl KvStore {
+    /// Opens a `KvStore` at the given path, replaying its log to rebuild
+    /// the in-memory index.
+    ///
+    /// Defaults to `Format::Json` for a data directory that doesn't exist
+    /// yet; see `open_with_format` to pick a different format.
+    pub fn open(path: impl Into<PathBuf>) -> Result<KvStore> {
+        KvStore::open_with_format(path, Format::default())
+    }
+
+    /// Opens a `KvStore` at the given path using `format` for any new log
+    /// files.
+    ///
+    /// If the data directory was already created with a different format,
+    /// that format is used instead so existing log files stay readable.
+    pub fn open_with_format(path: impl Into<PathBuf>, format: Format) -> Result<KvStore> {
+        Ok(KvStore(Arc::new(Mutex::new(KvStoreInner::open_with_format(
+            path, format,
+        )?))))
+    }
+}
+
+impl KvsEngine for KvStore {
+    fn set(&self, key: String, value: String) -> Result<()> {
+        self.0.lock().unwrap().set(key, value)
+    }
+
+    fn get(&self, key: String) -> Result<Option<String>> {
+        self.0.lock().unwrap().get(key)
+    }
+
+    fn remove(&self, key: String) -> Result<()> {
+        self.0.lock().unwrap().remove(key)
+    }
+}
+
+impl KvStoreInner {
+    fn open_with_format(path: impl Into<PathBuf>, requested_format: Format) -> Result<KvStoreInner> {
+        let path = path.into();
+        fs::create_dir_all(&path)?;
+
+        let format = match Format::read(&path)? {
+            Some(stored) => stored,
+            None => {
+                requested_format.write(&path)?;
+                requested_format
+            }
+        };
+
+        let mut store = HashMap::new();
+
+        let mut index = BTreeMap::new();
+
+        let mut uncompacted = 0;
+        let gen_list = sorted_gen_list(&path)?;
+        for &gen in &gen_list {
+            let mut reader = BufReaderWithPos::new(File::open(log_path(&path, gen))?)?;
+            uncompacted += load(format, gen, &mut reader, &mut index)?;
+            store.insert(gen, reader);
+        }
+
+        let current_generation = gen_list.last().unwrap_or(&0) + 1;
+
+        let log = new_log_file(&path, current_generation)?;
+        let reader = BufReaderWithPos::new(File::open(log_path(&path, current_generation))?)?;
+        store.insert(current_generation, reader);
+
+        Ok(KvStoreInner {
+            path,
+            store,
+            index,
+            log,
+            current_generation,
+            uncompacted,
+            format,
+        })
+    }
+
+    /// Clears stale entries in the log by rewriting every command still
+    /// reachable from `self.index` into a fresh generation, then dropping
+    /// every generation older than it.
+    fn compact(&mut self) -> Result<()> {
+        let compaction_gen = self.current_generation + 1;
+        let mut compaction_writer = new_log_file(&self.path, compaction_gen)?;
+        self.store.insert(
+            compaction_gen,
+            BufReaderWithPos::new(File::open(log_path(&self.path, compaction_gen))?)?,
+        );
+
+        let mut new_pos = 0;
+        for cmd_pos in &mut self.index.values_mut() {
+            let reader = self
+                .store
+                .get_mut(&cmd_pos.gen)
+                .expect("Can not get reader by cmd.gen");
+            if reader.pos != cmd_pos.pos {
+                reader.seek(SeekFrom::Start(cmd_pos.pos))?;
+            }
+
+            let mut entry_reader = reader.take(cmd_pos.len);
+            let len = io::copy(&mut entry_reader, &mut compaction_writer)?;
+            *cmd_pos = (compaction_gen, new_pos..new_pos + len).into();
+            new_pos += len;
+        }
+        compaction_writer.flush()?;
+
+        self.current_generation = compaction_gen + 1;
+        self.log = new_log_file(&self.path, self.current_generation)?;
+        self.store.insert(
+            self.current_generation,
+            BufReaderWithPos::new(File::open(log_path(&self.path, self.current_generation))?)?,
+        );
+
+        let stale_gens: Vec<_> = self
+            .store
+            .keys()
+            .filter(|&&gen| gen < compaction_gen)
+            .cloned()
+            .collect();
+        for stale_gen in stale_gens {
+            self.store.remove(&stale_gen);
+            fs::remove_file(log_path(&self.path, stale_gen))?;
+        }
+        self.uncompacted = 0;
+
+        Ok(())
+    }
+}
+
+impl KvStoreInner {
+    fn set(&mut self, key: String, val: String) -> Result<()> {
+        let pos = self.log.pos;
+        let cmd = Command::set(key.clone(), val.clone());
+        write_command(self.format, &mut self.log, &cmd)?;
+        self.log.flush()?;
+
+        if let Command::Set { key, .. } = cmd {
+            if let Some(old_cmd) = self
+                .index
+                .insert(key, (self.current_generation, pos..self.log.pos).into())
+            {
+                self.uncompacted += old_cmd.len;
+            }
+        }
+
+        if self.uncompacted > COMPACTION_THRESHOLD {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    fn get(&mut self, key: String) -> Result<Option<String>> {
+        if let Some(cmd) = self.index.get(&key) {
+            let reader = self
+                .store
+                .get_mut(&cmd.gen)
+                .expect("Can not get reader by cmd.gen");
+            reader.seek(SeekFrom::Start(cmd.pos))?;
+            if let Command::Set { value, .. } = read_command(self.format, reader, cmd.len)? {
+                Ok(Some(value))
+            } else {
+                Err(KvsError::UnexpectedCommandType)
+            }
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn remove(&mut self, key: String) -> Result<()> {
+        if self.index.contains_key(&key) {
+            let pos = self.log.pos;
+            let cmd = Command::remove(key);
+            write_command(self.format, &mut self.log, &cmd)?;
+            self.log.flush()?;
+            self.uncompacted += self.log.pos - pos;
+            if let Command::Remove { key } = cmd {
+                let old_cmd = self.index.remove(&key).expect("key not found");
+                self.uncompacted += old_cmd.len;
+            }
+
+            if self.uncompacted > COMPACTION_THRESHOLD {
+                self.compact()?;
+            }
+            Ok(())
+        } else {
+            Err(KvsError::KeyNotFound)
+        }
+    }
+}
+
+/// Serializes `cmd` into `writer` using `format`.
+///
+/// `Format::Bincode` has no streaming deserializer analogous to
+/// `serde_json`'s, so each record is length-prefixed with a 4-byte
+/// little-endian `u32` to let `load`/`read_command` recover its exact byte
+/// range without one.
+fn write_command<W: Write>(format: Format, writer: &mut W, cmd: &Command) -> Result<()> {
+    match format {
+        Format::Json => serde_json::to_writer(writer, cmd)?,
+        Format::Bincode => {
+            let payload = bincode::serialize(cmd)?;
+            writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+            writer.write_all(&payload)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a single command of `len` bytes, starting at the reader's current
+/// position, using `format`.
+fn read_command<R: Read>(format: Format, reader: &mut R, len: u64) -> Result<Command> {
+    match format {
+        Format::Json => Ok(serde_json::from_reader(reader.take(len))?),
+        Format::Bincode => {
+            let mut len_buf = [0u8; 4];
+            reader.read_exact(&mut len_buf)?;
+            let payload_len = u32::from_le_bytes(len_buf) as u64;
+            Ok(bincode::deserialize_from(reader.take(payload_len))?)
+        }
+    }
+}
+
+/// Replays the commands in the given generation's log into `index`,
+/// returning the number of bytes made stale by commands superseding an
+/// earlier entry (overwrites and removals).
+fn load(
+    format: Format,
+    gen: u64,
+    reader: &mut BufReaderWithPos<File>,
+    index: &mut BTreeMap<String, CommandPos>,
+) -> Result<u64> {
+    let mut pos = reader.seek(SeekFrom::Start(0))?;
+    let mut uncompacted = 0;
+    match format {
+        Format::Json => {
+            let mut stream = Deserializer::from_reader(reader).into_iter::<Command>();
+            while let Some(cmd) = stream.next() {
+                let new_pos = stream.byte_offset() as u64;
+                apply_loaded(cmd?, gen, pos, new_pos, index, &mut uncompacted);
+                pos = new_pos;
+            }
+        }
+        Format::Bincode => loop {
+            let mut len_buf = [0u8; 4];
+            match reader.read_exact(&mut len_buf) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+            let payload_len = u64::from(u32::from_le_bytes(len_buf));
+            let mut payload = vec![0u8; payload_len as usize];
+            reader.read_exact(&mut payload)?;
+            let new_pos = pos + 4 + payload_len;
+            apply_loaded(bincode::deserialize(&payload)?, gen, pos, new_pos, index, &mut uncompacted);
+            pos = new_pos;
+        },
+    }
+    Ok(uncompacted)
+}
+
+/// Applies a single replayed command to `index`, updating `uncompacted`
+/// with any bytes the command just made stale.
+fn apply_loaded(
+    cmd: Command,
+    gen: u64,
+    pos: u64,
+    new_pos: u64,
+    index: &mut BTreeMap<String, CommandPos>,
+    uncompacted: &mut u64,
+) {
+    match cmd {
+        Command::Set { key, .. } => {
+            if let Some(old_cmd) = index.insert(key, (gen, pos..new_pos).into()) {
+                *uncompacted += old_cmd.len;
+            }
+        }
+        Command::Remove { key } => {
+            let old_cmd = index.remove(&key).expect("key not in log file");
+            *uncompacted += old_cmd.len;
+            *uncompacted += new_pos - pos;
+        }
+    }
+}
+
+/// Returns sorted generation numbers in the given directory
+fn sorted_gen_list(path: &Path) -> Result<Vec<u64>> {
+    let mut gen_list: Vec<u64> = fs::read_dir(&path)?
+        .flat_map(|res| -> Result<_> { Ok(res?.path()) })
+        .filter(|path| path.is_file() && path.extension() == Some("log".as_ref()))
+        .flat_map(|path| {
+            path.file_name()
+                .and_then(OsStr::to_str)
+                .map(|s| s.trim_end_matches(".log"))
+                .map(str::parse::<u64>)
+        })
+        .flatten()
+        .collect();
+    gen_list.sort_unstable();
+    Ok(gen_list)
+}
+
+fn new_log_file(
+    path: &Path,
+    gen: u64,
+    // readers: &mut HashMap<u64, BufReaderWithPos<File>>,
+) -> Result<BufWriterWithPos<File>> {
+    let path = log_path(&path, gen);
+    let writer = BufWriterWithPos::new(
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(true)
+            .open(&path)?,
+    )?;
+    // readers.insert(gen, BufReaderWithPos::new(File::open(&path)?)?);
+    Ok(writer)
+}
+
+fn log_path(dir: &Path, gen: u64) -> PathBuf {
+    dir.join(format!("{:016}.log", gen))
+}
+
+/// Struct representing a command
+#[derive(Serialize, Deserialize, Debug)]
+enum Command {
+    Set { key: String, value: String },
+    Remove { key: String },
+}
+
+impl Command {
+    fn set(key: String, value: String) -> Command {
+        Command::Set { key, value }
+    }
+
+    fn remove(key: String) -> Command {
+        Command::Remove { key }
+    }
+}
+
+/// Represents the position and length of a serialized command in the log,
+/// including the length prefix for `Format::Bincode`
+#[derive(Debug)]
+struct CommandPos {
+    gen: u64,
+    pos: u64,
+    len: u64,
+}
+
+impl From<(u64, Range<u64>)> for CommandPos {
+    fn from((gen, range): (u64, Range<u64>)) -> Self {
+        CommandPos {
+            gen,
+            pos: range.start,
+            len: range.end - range.start,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BufReaderWithPos<R: Read + Seek> {
+    reader: BufReader<R>,
+    pos: u64,
+}
+
+impl<R: Read + Seek> BufReaderWithPos<R> {
+    fn new(mut inner: R) -> Result<Self> {
+        let pos = inner.seek(SeekFrom::Current(0))?;
+        Ok(BufReaderWithPos {
+            reader: BufReader::new(inner),
+            pos,
+        })
+    }
+}
+
+impl<R: Read + Seek> Read for BufReaderWithPos<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl<R: Read + Seek> Seek for BufReaderWithPos<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = self.reader.seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+#[derive(Debug)]
+struct BufWriterWithPos<W: Write + Seek> {
+    writer: BufWriter<W>,
+    pos: u64,
+}
+
+impl<W: Write + Seek> BufWriterWithPos<W> {
+    fn new(mut inner: W) -> Result<Self> {
+        let pos = inner.seek(SeekFrom::Current(0))?;
+        Ok(BufWriterWithPos {
+            writer: BufWriter::new(inner),
+            pos,
+        })
+    }
+}
+
+impl<W: Write + Seek> Write for BufWriterWithPos<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.writer.write(buf)?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write + Seek> Seek for BufWriterWithPos<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = self.writer.seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+type LogManagement<W: Write + Seek> = BufWriterWithPos<W>;
+
+#[derive(Debug, PartialEq)]
+struct Filename {
+    general: u64,
+    valid: bool,
+}
+
+impl Filename {
+    pub fn new(general: u64) -> Filename {
+        Filename {
+            general,
+            valid: true,
+        }
+    }
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+}
+
+impl fmt::Display for Filename {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016}", self.general)
+    }
+}
+
+impl From<&str> for Filename {
+    fn from(general: &str) -> Self {
+        match general.parse::<u64>() {
+            Ok(general) => Filename {
+                general,
+                valid: true,
+            },
+            Err(_) => Filename {
+                general: 0,
+                valid: false,
+            },
+        }
+    }
+}
+
+impl FromStr for Filename {
+    type Err = ParseIntError;
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        Ok(Filename::from(s))
+    }
+}
+
+#[cfg(test)]
+mod test_kv {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_log_to_file() {
+        let store = KvStore::open("data").unwrap();
+        store.set("key".to_owned(), "val".to_owned()).unwrap();
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!("00000000000000000000000000000001", format!("{:032}", 1));
+        assert_eq!(Filename::new(1), "0000000000000001".parse().unwrap());
+        let filename = Filename::from("0000000000000001");
+        assert_eq!(Filename::new(1), filename);
+    }
+
+    fn dir_size(path: &Path) -> u64 {
+        fs::read_dir(path)
+            .unwrap()
+            .flat_map(|res| res.map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension() == Some("log".as_ref()))
+            .fold(0, |acc, path| acc + fs::metadata(path).unwrap().len())
+    }
+
+    #[test]
+    fn compaction_shrinks_log_on_repeated_overwrites() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = KvStore::open(temp_dir.path()).unwrap();
+
+        let mut sizes = Vec::new();
+        for iter in 0..60_000u64 {
+            store
+                .set("key".to_owned(), format!("value-{}", iter))
+                .unwrap();
+            if iter % 1000 == 0 {
+                sizes.push(dir_size(temp_dir.path()));
+            }
+        }
+
+        assert!(
+            sizes.windows(2).any(|w| w[1] < w[0]),
+            "expected at least one compaction to shrink the on-disk log, sizes recorded: {:?}",
+            sizes
+        );
+        assert_eq!(
+            store.get("key".to_owned()).unwrap(),
+            Some("value-59999".to_owned())
+        );
+    }
+
+    #[test]
+    fn format_round_trip_through_reopen() {
+        for format in [Format::Json, Format::Bincode] {
+            let temp_dir = TempDir::new().unwrap();
+            let store = KvStore::open_with_format(temp_dir.path(), format).unwrap();
+            store.set("key".to_owned(), "value".to_owned()).unwrap();
+            drop(store);
+
+            // Reopening with the other format is ignored: the directory
+            // remembers the format it was created with.
+            let other = match format {
+                Format::Json => Format::Bincode,
+                Format::Bincode => Format::Json,
+            };
+            let reopened = KvStore::open_with_format(temp_dir.path(), other).unwrap();
+            assert_eq!(
+                reopened.get("key".to_owned()).unwrap(),
+                Some("value".to_owned())
+            );
+        }
+    }
+
+    #[test]
+    fn format_size_comparison() {
+        let json_dir = TempDir::new().unwrap();
+        let json_store = KvStore::open_with_format(json_dir.path(), Format::Json).unwrap();
+
+        let bincode_dir = TempDir::new().unwrap();
+        let bincode_store =
+            KvStore::open_with_format(bincode_dir.path(), Format::Bincode).unwrap();
+
+        for i in 0..1000 {
+            let key = format!("key{}", i);
+            let value = format!("value{}", i);
+            json_store.set(key.clone(), value.clone()).unwrap();
+            bincode_store.set(key, value).unwrap();
+        }
+
+        let json_size = dir_size(json_dir.path());
+        let bincode_size = dir_size(bincode_dir.path());
+        assert_ne!(
+            json_size, bincode_size,
+            "expected the two formats to encode the same commands at different sizes"
+        );
+    }
+}
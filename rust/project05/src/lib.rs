@@ -0,0 +1,19 @@
+#[macro_use]
+extern crate log;
+
+pub use engine::KvsEngine;
+pub use error::{KvsError, Result};
+pub use format::Format;
+pub use kv::KvStore;
+pub use protocol::{Request, Response};
+pub use server::KvsServer;
+pub use sled_engine::SledKvsEngine;
+
+mod engine;
+mod error;
+mod format;
+mod kv;
+mod protocol;
+mod server;
+mod sled_engine;
+pub mod thread_pool;
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+/// A request sent from a `kvs-client` to a `kvs-server`.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Request {
+    /// Get the string value of a given string key.
+    Get {
+        /// The key to look up.
+        key: String,
+    },
+    /// Set the value of a string key to a string.
+    Set {
+        /// The key to set.
+        key: String,
+        /// The value to associate with the key.
+        value: String,
+    },
+    /// Remove a given key.
+    Remove {
+        /// The key to remove.
+        key: String,
+    },
+}
+
+/// A response sent from a `kvs-server` back to a `kvs-client`.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Response {
+    /// The request succeeded, carrying a value for `Get` or `None` for
+    /// `Set`/`Remove`.
+    Ok(Option<String>),
+    /// The request failed; the string is the error's `Display` output.
+    Err(String),
+}
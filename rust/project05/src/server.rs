@@ -0,0 +1,68 @@
+use crate::thread_pool::ThreadPool;
+use crate::{KvsEngine, Request, Response, Result};
+use serde_json::Deserializer;
+use std::io::{BufReader, BufWriter, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+/// A server that accepts connections speaking the `kvs` wire protocol and
+/// dispatches each request to a `KvsEngine`, handing each connection off
+/// to `ThreadPool` so that slow clients don't serialize one another.
+pub struct KvsServer<E: KvsEngine, P: ThreadPool> {
+    engine: E,
+    pool: P,
+}
+
+impl<E: KvsEngine, P: ThreadPool> KvsServer<E, P> {
+    /// Creates a `KvsServer` that serves requests against `engine`, running
+    /// each connection on `pool`.
+    pub fn new(engine: E, pool: P) -> Self {
+        KvsServer { engine, pool }
+    }
+
+    /// Listens on `addr`, handing off connections to the thread pool until
+    /// the process is killed.
+    pub fn run(self, addr: impl ToSocketAddrs) -> Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        for stream in listener.incoming() {
+            let engine = self.engine.clone();
+            match stream {
+                Ok(stream) => self.pool.spawn(move || {
+                    if let Err(e) = serve(engine, stream) {
+                        error!("Error serving client: {}", e);
+                    }
+                }),
+                Err(e) => error!("Connection failed: {}", e),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn serve<E: KvsEngine>(engine: E, stream: TcpStream) -> Result<()> {
+    let peer_addr = stream.peer_addr()?;
+    let reader = BufReader::new(&stream);
+    let mut writer = BufWriter::new(&stream);
+    let requests = Deserializer::from_reader(reader).into_iter::<Request>();
+
+    for request in requests {
+        let request = request?;
+        debug!("Received request from {}: {:?}", peer_addr, request);
+        let response = match request {
+            Request::Get { key } => match engine.get(key) {
+                Ok(value) => Response::Ok(value),
+                Err(e) => Response::Err(e.to_string()),
+            },
+            Request::Set { key, value } => match engine.set(key, value) {
+                Ok(()) => Response::Ok(None),
+                Err(e) => Response::Err(e.to_string()),
+            },
+            Request::Remove { key } => match engine.remove(key) {
+                Ok(()) => Response::Ok(None),
+                Err(e) => Response::Err(e.to_string()),
+            },
+        };
+        serde_json::to_writer(&mut writer, &response)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,22 @@
+pub use self::naive::NaiveThreadPool;
+pub use self::shared_queue::SharedQueueThreadPool;
+
+mod naive;
+mod shared_queue;
+
+use crate::Result;
+
+/// A pool of worker threads that jobs can be submitted to.
+pub trait ThreadPool: Sized {
+    /// Creates a new thread pool with `threads` worker threads.
+    fn new(threads: u32) -> Result<Self>;
+
+    /// Spawns a job to be run on one of the pool's threads.
+    ///
+    /// Spawning the job does not wait for it to finish, and a job that
+    /// panics does not shrink the pool or propagate the panic to the
+    /// caller.
+    fn spawn<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static;
+}
@@ -0,0 +1,19 @@
+use super::ThreadPool;
+use crate::Result;
+use std::thread;
+
+/// A `ThreadPool` that spawns a brand new thread for every job.
+pub struct NaiveThreadPool;
+
+impl ThreadPool for NaiveThreadPool {
+    fn new(_threads: u32) -> Result<Self> {
+        Ok(NaiveThreadPool)
+    }
+
+    fn spawn<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        thread::spawn(job);
+    }
+}
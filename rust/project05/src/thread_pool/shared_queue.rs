@@ -0,0 +1,70 @@
+use super::ThreadPool;
+use crate::Result;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A `ThreadPool` with a fixed number of worker threads pulling jobs off a
+/// shared queue. A worker thread that panics while running a job is
+/// replaced so the pool's thread count never shrinks.
+pub struct SharedQueueThreadPool {
+    tx: Sender<Job>,
+}
+
+impl ThreadPool for SharedQueueThreadPool {
+    fn new(threads: u32) -> Result<Self> {
+        let (tx, rx) = channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+
+        for _ in 0..threads {
+            Worker(Arc::clone(&rx)).spawn();
+        }
+
+        Ok(SharedQueueThreadPool { tx })
+    }
+
+    fn spawn<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.tx
+            .send(Box::new(job))
+            .expect("the thread pool's worker threads have all shut down");
+    }
+}
+
+/// Holds the shared job queue for one worker thread.
+///
+/// Dropping a `Worker` while the thread is panicking spawns its
+/// replacement, which is how a job panic turns into a respawned worker
+/// instead of a thread permanently lost from the pool.
+struct Worker(Arc<Mutex<Receiver<Job>>>);
+
+impl Worker {
+    fn spawn(self) {
+        thread::spawn(move || self.run());
+    }
+
+    fn run(&self) {
+        loop {
+            let job = {
+                let rx = self.0.lock().unwrap();
+                rx.recv()
+            };
+            match job {
+                Ok(job) => job(),
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            Worker(Arc::clone(&self.0)).spawn();
+        }
+    }
+}